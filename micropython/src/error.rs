@@ -0,0 +1,61 @@
+use std::{ffi::c_void, fmt};
+
+use micropython_sys as mp_sys;
+
+/// An exception raised while compiling or running a script.
+///
+/// The originating `mp_obj_t` cannot outlive the `nlr` unwind that produced
+/// it, so `PyError` instead captures its type name and rendered message as
+/// owned strings at the point it's caught.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PyError {
+    pub type_name: String,
+    pub message: String,
+}
+
+impl PyError {
+    /// Captures `exception` (as found in `nlr.ret_val` after a failed
+    /// `nlr_push`) into an owned `PyError`.
+    ///
+    /// # Safety
+    /// `exception` must be a live `mp_obj_t` pointing at an exception
+    /// instance, as produced by the `nlr` unwind mechanism.
+    pub(crate) unsafe fn capture(exception: mp_sys::mp_obj_t) -> Self {
+        let ty = mp_sys::mp_obj_get_type(exception);
+        let name_ptr = mp_sys::qstr_str((*ty).name);
+        let type_name = std::ffi::CStr::from_ptr(name_ptr)
+            .to_string_lossy()
+            .into_owned();
+
+        let mut buffer = MessageBuffer::default();
+        let print = mp_sys::mp_print_t {
+            data: &mut buffer as *mut MessageBuffer as *mut c_void,
+            print_strn: Some(print_to_buffer),
+        };
+        // `PRINT_STR` renders only the exception's arguments, not the
+        // traceback or the type name (which is kept separately above).
+        mp_sys::mp_obj_print_helper(&print, exception, mp_sys::mp_print_kind_t_PRINT_STR);
+
+        Self {
+            type_name,
+            message: String::from_utf8_lossy(&buffer.0).into_owned(),
+        }
+    }
+}
+
+impl fmt::Display for PyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.type_name, self.message)
+    }
+}
+
+impl std::error::Error for PyError {}
+
+#[derive(Default)]
+struct MessageBuffer(Vec<u8>);
+
+unsafe extern "C" fn print_to_buffer(data: *mut c_void, str_: *const core::ffi::c_char, len: usize) {
+    let buffer = &mut *(data as *mut MessageBuffer);
+    let bytes = core::slice::from_raw_parts(str_ as *const u8, len);
+    buffer.0.extend_from_slice(bytes);
+}
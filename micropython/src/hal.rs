@@ -0,0 +1,87 @@
+use std::cell::RefCell;
+
+/// Captures or redirects a VM's stdio.
+///
+/// An installed `Hal` receives every byte the running script prints and is
+/// asked for every byte it reads, letting embedders log output, feed a
+/// REPL's input, or buffer results for assertions instead of going through a
+/// real tty.
+pub trait Hal {
+    fn write_bytes(&mut self, bytes: &[u8]);
+    fn read_byte(&mut self) -> Option<u8>;
+}
+
+/// The default [`Hal`]: writes go to the process's stdout, reads never
+/// produce a byte. Matches the VM's behavior before a `Hal` is installed.
+struct StdioHal;
+
+impl Hal for StdioHal {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        if let Ok(s) = core::str::from_utf8(bytes) {
+            print!("{s}");
+        }
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        None
+    }
+}
+
+thread_local! {
+    static HAL: RefCell<Box<dyn Hal>> = RefCell::new(Box::new(StdioHal));
+}
+
+/// Installs `hal` as the active thread's VM stdio, replacing whatever was
+/// previously installed (`StdioHal` by default).
+pub fn install_hal(hal: impl Hal + 'static) {
+    HAL.with(|cell| *cell.borrow_mut() = Box::new(hal));
+}
+
+/// # Safety
+/// `string` must point to a valid, initialized buffer of at least `len`
+/// bytes, as guaranteed by the MicroPython runtime calling this HAL entry
+/// point.
+#[no_mangle]
+pub unsafe extern "C" fn mp_hal_stdout_tx_strn_cooked(string: *const u8, len: usize) {
+    let bytes = unsafe { core::slice::from_raw_parts(string, len) };
+    HAL.with(|cell| cell.borrow_mut().write_bytes(bytes));
+}
+
+#[no_mangle]
+pub extern "C" fn mp_hal_stdin_rx_chr() -> core::ffi::c_int {
+    HAL.with(|cell| cell.borrow_mut().read_byte())
+        .map(|b| b as core::ffi::c_int)
+        .unwrap_or(-1)
+}
+
+/// A [`Hal`] that accumulates written bytes into a `String` instead of
+/// printing them, so tests and REPL front-ends can assert on what a script
+/// printed.
+#[derive(Debug, Default)]
+pub struct CapturingHal {
+    output: String,
+}
+
+impl CapturingHal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+}
+
+impl Hal for CapturingHal {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.output.push_str(&String::from_utf8_lossy(bytes));
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        None
+    }
+}
@@ -1,9 +1,43 @@
-use std::{marker::PhantomData, pin::Pin};
+use std::{cell::RefCell, marker::PhantomData, pin::Pin};
 
 use micropython_sys as mp_sys;
 
+mod error;
+mod hal;
+mod module;
+mod value;
+
+pub use error::PyError;
+pub use hal::{install_hal, CapturingHal, Hal};
+pub use module::{ModuleBuilder, TooManyNativeFunctions};
+pub use value::{Value, ValueIter};
+
 pub type QStr = u32;
 
+/// Supplies additional memory regions to the GC when the initial heap fills up.
+///
+/// Implementors back the VM with whatever backing store makes sense for the
+/// embedder: a bump arena, the global allocator, or a statically reserved
+/// pool of regions handed out as needed. This lets long-running scripts grow
+/// past the VM's initial `HEAP_SIZE` without recompiling.
+pub trait HeapProvider {
+    /// Hand back a region of at least `min_bytes`, or `None` if no more
+    /// memory can be made available.
+    fn claim(&mut self, min_bytes: usize) -> Option<&'static mut [u8]>;
+}
+
+/// A [`HeapProvider`] that never has anything to offer.
+///
+/// This is the default used by [`Vm::new`] for VMs that aren't configured to
+/// grow their heap.
+pub struct NoHeapProvider;
+
+impl HeapProvider for NoHeapProvider {
+    fn claim(&mut self, _min_bytes: usize) -> Option<&'static mut [u8]> {
+        None
+    }
+}
+
 pub struct VmState<const HEAP_SIZE: usize> {
     heap: [u8; HEAP_SIZE],
     stack_top: core::ffi::c_int,
@@ -24,63 +58,138 @@ impl<const HEAP_SIZE: usize> Default for VmState<HEAP_SIZE> {
     }
 }
 
+/// How many times `compile`/`run_protected` will ask the [`HeapProvider`]
+/// for another region before giving up and surfacing the OOM as an error.
+///
+/// `grow_heap` always requests a `HEAP_SIZE`-sized region since MicroPython's
+/// `nlr.ret_val` doesn't expose how big the allocation that failed actually
+/// was. A provider backed by a bump arena or the global allocator can keep
+/// handing out fresh regions indefinitely, so without a cap a single
+/// allocation bigger than one region would retry forever, each new
+/// `HEAP_SIZE`-sized chunk still too small on its own to satisfy it.
+const MAX_GROW_ATTEMPTS: usize = 8;
+
 pub struct Vm<'state, const HEAP_SIZE: usize> {
     _state: Pin<&'state mut VmState<HEAP_SIZE>>,
+    heap_provider: RefCell<Box<dyn HeapProvider>>,
 }
 
 impl<'state, const HEAP_SIZE: usize> Vm<'state, HEAP_SIZE> {
-    pub fn new(mut state: Pin<&'state mut VmState<HEAP_SIZE>>) -> Self {
+    pub fn new(state: Pin<&'state mut VmState<HEAP_SIZE>>) -> Self {
+        Self::with_heap_provider(state, NoHeapProvider)
+    }
+
+    /// Like [`Vm::new`], but grows the GC heap by asking `heap_provider` for
+    /// additional regions (via `gc_add`) instead of failing allocation once
+    /// the initial `HEAP_SIZE` is exhausted. Requires the port to be built
+    /// with `Config::split_heap(true)` so `MICROPY_GC_SPLIT_HEAP` is enabled.
+    pub fn with_heap_provider(
+        mut state: Pin<&'state mut VmState<HEAP_SIZE>>,
+        heap_provider: impl HeapProvider + 'static,
+    ) -> Self {
         unsafe {
             mp_sys::mp_stack_set_top(&mut state.stack_top as *mut i32 as _);
-            let stack_top = &mut state.heap as *mut u8;
-            mp_sys::gc_init(stack_top as _, stack_top.add(HEAP_SIZE) as _);
+            let heap = &mut state.heap as *mut u8;
+            mp_sys::gc_init(heap as _, heap.add(HEAP_SIZE) as _);
             mp_sys::mp_init();
         }
-        Self { _state: state }
+        Self {
+            _state: state,
+            heap_provider: RefCell::new(Box::new(heap_provider)),
+        }
     }
 
-    pub fn compile<'vm>(&'vm self, source: QStr, code: &str) -> Option<Object<'vm>> {
-        let mut nlr = core::mem::MaybeUninit::uninit();
-        let ret = unsafe { mp_sys::nlr_push(nlr.as_mut_ptr()) };
-        if ret == 0 {
-            unsafe {
-                let lex = mp_sys::mp_lexer_new_from_str_len(
-                    source as _,
-                    code.as_ptr() as _,
-                    code.len(),
-                    0,
-                );
-                let source_name = (*lex).source_name;
-                let mut parse_tree =
-                    mp_sys::mp_parse(lex, mp_sys::mp_parse_input_kind_t_MP_PARSE_FILE_INPUT);
-                let object = mp_sys::mp_compile(&mut parse_tree as _, source_name, true);
-                mp_sys::nlr_pop();
-                Some(Object {
-                    object,
-                    _phantom: PhantomData,
-                })
+    /// Asks the configured [`HeapProvider`] for a new region of at least
+    /// `min_bytes` and registers it with the GC. Returns whether a new
+    /// region was added.
+    fn grow_heap(&self, min_bytes: usize) -> bool {
+        let Some(region) = self.heap_provider.borrow_mut().claim(min_bytes) else {
+            return false;
+        };
+        let start = region.as_mut_ptr();
+        unsafe { mp_sys::gc_add(start as _, start.add(region.len()) as _) };
+        true
+    }
+
+    fn is_oom(exception: mp_sys::mp_obj_t) -> bool {
+        unsafe { mp_sys::mp_obj_is_type(exception, &mp_sys::mp_type_MemoryError as *const _ as _) }
+    }
+
+    /// Runs `f` under the VM's exception/OOM-retry machinery and wraps its
+    /// result as a [`Value`]. `exec` and `import_module` are both "call into
+    /// the VM, get an `mp_obj_t` back" operations that only differ in what
+    /// they call; `compile` doesn't fit this shape since it returns an
+    /// [`Object`] rather than a [`Value`], so it keeps its own loop.
+    fn run_protected<'vm>(
+        &'vm self,
+        f: impl FnOnce() -> mp_sys::mp_obj_t,
+    ) -> Result<Value<'vm>, PyError> {
+        let mut grow_attempts_left = MAX_GROW_ATTEMPTS;
+        loop {
+            let mut nlr = core::mem::MaybeUninit::uninit();
+            let ret = unsafe { mp_sys::nlr_push(nlr.as_mut_ptr()) };
+            if ret == 0 {
+                let result = f();
+                unsafe { mp_sys::nlr_pop() };
+                return Ok(Value::wrap(result));
             }
-        } else {
+
             let nlr = unsafe { nlr.assume_init() };
-            unsafe {
-                mp_sys::mp_obj_print_exception(&mp_sys::mp_plat_print as _, nlr.ret_val as _)
-            };
-            None
+            if Self::is_oom(nlr.ret_val) && grow_attempts_left > 0 && self.grow_heap(HEAP_SIZE) {
+                grow_attempts_left -= 1;
+                continue;
+            }
+            return Err(unsafe { PyError::capture(nlr.ret_val) });
         }
     }
 
-    pub fn exec<'vm>(&'vm self, object: &mut Object<'vm>) {
-        let mut nlr = core::mem::MaybeUninit::uninit();
-        let ret = unsafe { mp_sys::nlr_push(nlr.as_mut_ptr()) };
-        if ret == 0 {
-            unsafe { mp_sys::mp_call_function_0(object.object) };
-        } else {
+    /// Imports the module named `name` (as interned via `Config::qstr` at
+    /// build time) and returns the resulting module object, via
+    /// `mp_import_name` — the same path `import <name>` takes inside a
+    /// running script.
+    pub fn import_module<'vm>(&'vm self, name: QStr) -> Result<Value<'vm>, PyError> {
+        self.run_protected(|| unsafe {
+            mp_sys::mp_import_name(name as _, mp_sys::mp_const_none, mp_sys::MP_OBJ_NEW_SMALL_INT(0) as _)
+        })
+    }
+
+    pub fn compile<'vm>(&'vm self, source: QStr, code: &str) -> Result<Object<'vm>, PyError> {
+        let mut grow_attempts_left = MAX_GROW_ATTEMPTS;
+        loop {
+            let mut nlr = core::mem::MaybeUninit::uninit();
+            let ret = unsafe { mp_sys::nlr_push(nlr.as_mut_ptr()) };
+            if ret == 0 {
+                unsafe {
+                    let lex = mp_sys::mp_lexer_new_from_str_len(
+                        source as _,
+                        code.as_ptr() as _,
+                        code.len(),
+                        0,
+                    );
+                    let source_name = (*lex).source_name;
+                    let mut parse_tree =
+                        mp_sys::mp_parse(lex, mp_sys::mp_parse_input_kind_t_MP_PARSE_FILE_INPUT);
+                    let object = mp_sys::mp_compile(&mut parse_tree as _, source_name, true);
+                    mp_sys::nlr_pop();
+                    return Ok(Object {
+                        object,
+                        _phantom: PhantomData,
+                    });
+                }
+            }
+
             let nlr = unsafe { nlr.assume_init() };
-            unsafe {
-                mp_sys::mp_obj_print_exception(&mp_sys::mp_plat_print as _, nlr.ret_val as _)
-            };
+            if Self::is_oom(nlr.ret_val) && grow_attempts_left > 0 && self.grow_heap(HEAP_SIZE) {
+                grow_attempts_left -= 1;
+                continue;
+            }
+            return Err(unsafe { PyError::capture(nlr.ret_val) });
         }
     }
+
+    pub fn exec<'vm>(&'vm self, object: &mut Object<'vm>) -> Result<Value<'vm>, PyError> {
+        self.run_protected(|| unsafe { mp_sys::mp_call_function_0(object.object) })
+    }
 }
 
 impl<'state, const HEAP_SIZE: usize> Drop for Vm<'state, HEAP_SIZE> {
@@ -0,0 +1,251 @@
+use std::cell::RefCell;
+
+use micropython_sys as mp_sys;
+
+use crate::error::PyError;
+use crate::value::Value;
+
+/// How many Rust-native functions can be registered on one thread. Each one
+/// claims a fixed C-ABI trampoline out of this pool, since MicroPython's
+/// builtin function objects call through a bare function pointer with no
+/// room for a closure environment.
+///
+/// The slot table is thread-local: this ceiling is shared by every
+/// [`ModuleBuilder`] on the same thread and slots are never freed once
+/// claimed, so registering more than [`MAX_NATIVE_FUNCTIONS`] functions in
+/// total on one thread (across however many modules) will have
+/// [`ModuleBuilder::register`] return [`TooManyNativeFunctions`].
+pub const MAX_NATIVE_FUNCTIONS: usize = 16;
+
+/// A native function's arguments (and its return value) are only valid for
+/// the duration of the trampoline call that produced them (see
+/// `call_native`), so the lifetime is universally quantified per call rather
+/// than `'static` — a native function can't smuggle an argument out into a
+/// `static` or an accumulating `Vec`.
+type NativeFn = Box<dyn for<'a> Fn(&[Value<'a>]) -> Result<Value<'a>, PyError>>;
+
+/// Returned by [`ModuleBuilder::register`] when all [`MAX_NATIVE_FUNCTIONS`]
+/// trampoline slots are already claimed.
+#[derive(Debug)]
+pub struct TooManyNativeFunctions;
+
+impl std::fmt::Display for TooManyNativeFunctions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "all {MAX_NATIVE_FUNCTIONS} native function trampoline slots are in use")
+    }
+}
+
+impl std::error::Error for TooManyNativeFunctions {}
+
+thread_local! {
+    static NATIVE_FUNCTIONS: RefCell<Vec<Option<NativeFn>>> =
+        RefCell::new((0..MAX_NATIVE_FUNCTIONS).map(|_| None).collect());
+}
+
+fn claim_slot(f: NativeFn) -> Result<usize, TooManyNativeFunctions> {
+    NATIVE_FUNCTIONS.with(|functions| {
+        let mut functions = functions.borrow_mut();
+        let slot = functions
+            .iter()
+            .position(Option::is_none)
+            .ok_or(TooManyNativeFunctions)?;
+        functions[slot] = Some(f);
+        Ok(slot)
+    })
+}
+
+fn call_native(slot: usize, n_args: usize, args: *const mp_sys::mp_obj_t) -> mp_sys::mp_obj_t {
+    let args = unsafe { core::slice::from_raw_parts(args, n_args) };
+    let args: Vec<Value<'_>> = args.iter().copied().map(Value::wrap).collect();
+
+    let result = NATIVE_FUNCTIONS.with(|functions| {
+        let functions = functions.borrow();
+        let f = functions[slot]
+            .as_ref()
+            .expect("native function slot is registered before it's called");
+        f(&args)
+    });
+
+    match result {
+        Ok(value) => value.into_raw(),
+        Err(err) => raise(&err),
+    }
+}
+
+/// Raises `err` as a MicroPython exception. Does not return, matching the
+/// semantics of `nlr_jump`, but is typed as diverging-in-spirit rather than
+/// `!` since the underlying C call isn't known to Rust to be `noreturn`.
+fn raise(err: &PyError) -> mp_sys::mp_obj_t {
+    let message = std::ffi::CString::new(err.message.as_str()).unwrap_or_default();
+    unsafe {
+        mp_sys::mp_raise_msg(&mp_sys::mp_type_RuntimeError as *const _ as _, message.as_ptr() as _);
+    }
+    unreachable!("mp_raise_msg longjmps out of this function")
+}
+
+macro_rules! define_trampoline {
+    ($name:ident, $slot:expr) => {
+        unsafe extern "C" fn $name(n_args: usize, args: *const mp_sys::mp_obj_t) -> mp_sys::mp_obj_t {
+            call_native($slot, n_args, args)
+        }
+    };
+}
+
+define_trampoline!(trampoline_0, 0);
+define_trampoline!(trampoline_1, 1);
+define_trampoline!(trampoline_2, 2);
+define_trampoline!(trampoline_3, 3);
+define_trampoline!(trampoline_4, 4);
+define_trampoline!(trampoline_5, 5);
+define_trampoline!(trampoline_6, 6);
+define_trampoline!(trampoline_7, 7);
+define_trampoline!(trampoline_8, 8);
+define_trampoline!(trampoline_9, 9);
+define_trampoline!(trampoline_10, 10);
+define_trampoline!(trampoline_11, 11);
+define_trampoline!(trampoline_12, 12);
+define_trampoline!(trampoline_13, 13);
+define_trampoline!(trampoline_14, 14);
+define_trampoline!(trampoline_15, 15);
+
+type Trampoline = unsafe extern "C" fn(usize, *const mp_sys::mp_obj_t) -> mp_sys::mp_obj_t;
+
+const TRAMPOLINES: [Trampoline; MAX_NATIVE_FUNCTIONS] = [
+    trampoline_0,
+    trampoline_1,
+    trampoline_2,
+    trampoline_3,
+    trampoline_4,
+    trampoline_5,
+    trampoline_6,
+    trampoline_7,
+    trampoline_8,
+    trampoline_9,
+    trampoline_10,
+    trampoline_11,
+    trampoline_12,
+    trampoline_13,
+    trampoline_14,
+    trampoline_15,
+];
+
+/// Builds an `mp_obj_module_t` out of Rust closures and registers it so
+/// `import <name>` resolves inside a running [`Vm`](crate::Vm).
+///
+/// Qstrs passed here (the module's own name, and each function's name) must
+/// already be interned, e.g. via `Config::qstr` at build time.
+pub struct ModuleBuilder {
+    name: mp_sys::qstr,
+    functions: Vec<(mp_sys::qstr, NativeFn)>,
+}
+
+impl ModuleBuilder {
+    pub fn new(name: mp_sys::qstr) -> Self {
+        Self {
+            name,
+            functions: Vec::new(),
+        }
+    }
+
+    /// Registers a function, callable from Python as `<module>.<name>(...)`.
+    pub fn function(
+        mut self,
+        name: mp_sys::qstr,
+        f: impl for<'a> Fn(&[Value<'a>]) -> Result<Value<'a>, PyError> + 'static,
+    ) -> Self {
+        self.functions.push((name, Box::new(f)));
+        self
+    }
+
+    /// Builds the module and registers it in `sys.modules` so it's found by
+    /// `import` without needing a matching `MP_REGISTER_MODULE` in C.
+    ///
+    /// Leaks the module's globals dict and `mp_obj_module_t` for `'static`,
+    /// matching the lifetime MicroPython's own builtin modules are given.
+    ///
+    /// # Errors
+    /// Returns [`TooManyNativeFunctions`] if registering this module's
+    /// functions would exceed this thread's [`MAX_NATIVE_FUNCTIONS`]
+    /// trampoline pool.
+    pub fn register(self) -> Result<(), TooManyNativeFunctions> {
+        let globals = unsafe {
+            let dict = mp_sys::mp_obj_new_dict(self.functions.len() as _);
+            mp_sys::mp_obj_dict_store(
+                dict,
+                mp_sys::MP_OBJ_NEW_QSTR(mp_sys::MP_QSTR___name__) as _,
+                mp_sys::MP_OBJ_NEW_QSTR(self.name) as _,
+            );
+            dict
+        };
+        // The dict lives on the GC heap but the module object below does not,
+        // so the dict is leaked by holding a GC root slot that is never released.
+        core::mem::forget(Value::wrap(globals));
+
+        for (qstr, f) in self.functions {
+            let slot = claim_slot(f)?;
+            let fun_obj = Box::leak(Box::new(mp_sys::mp_obj_fun_builtin_var_t {
+                base: mp_sys::mp_obj_base_t {
+                    type_: unsafe { &mp_sys::mp_type_fun_builtin_var },
+                },
+                n_args_min: 0,
+                n_args_max: mp_sys::MP_OBJ_FUN_ARGS_MAX,
+                fun: mp_sys::mp_obj_fun_builtin_var_t__bindgen_ty_1 {
+                    var: Some(TRAMPOLINES[slot]),
+                },
+            }));
+            unsafe {
+                mp_sys::mp_obj_dict_store(
+                    globals,
+                    mp_sys::MP_OBJ_NEW_QSTR(qstr) as _,
+                    fun_obj as *mut _ as _,
+                );
+            }
+        }
+
+        let module = Box::leak(Box::new(mp_sys::mp_obj_module_t {
+            base: mp_sys::mp_obj_base_t {
+                type_: unsafe { &mp_sys::mp_type_module },
+            },
+            globals: globals as _,
+        }));
+
+        unsafe {
+            mp_sys::mp_obj_dict_store(
+                mp_sys::mp_module_get_loaded_dict() as _,
+                mp_sys::MP_OBJ_NEW_QSTR(self.name) as _,
+                module as *mut _ as _,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dummy_fn() -> NativeFn {
+        Box::new(|_args| {
+            Err(PyError {
+                type_name: String::new(),
+                message: String::new(),
+            })
+        })
+    }
+
+    #[test]
+    fn claim_slot_fills_the_pool_then_errors() {
+        let mut slots = Vec::new();
+        for _ in 0..MAX_NATIVE_FUNCTIONS {
+            slots.push(claim_slot(dummy_fn()).expect("a slot should still be free"));
+        }
+
+        assert!(matches!(claim_slot(dummy_fn()), Err(TooManyNativeFunctions)));
+
+        // Every claimed slot is distinct.
+        slots.sort_unstable();
+        slots.dedup();
+        assert_eq!(slots.len(), MAX_NATIVE_FUNCTIONS);
+    }
+}
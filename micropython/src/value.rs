@@ -0,0 +1,169 @@
+use std::cell::RefCell;
+use std::marker::PhantomData;
+
+use micropython_sys as mp_sys;
+
+/// How many `Value`/`ValueIter` roots can be held live at once.
+///
+/// Must match the array size `templates/root_pointers.h.tmpl` declares via
+/// `MICROPY_PORT_ROOT_POINTERS`, since that's the backing storage the GC
+/// actually scans (see [`claim_root`]).
+const MAX_ROOTS: usize = 256;
+
+thread_local! {
+    static FREE_ROOTS: RefCell<Vec<usize>> = RefCell::new((0..MAX_ROOTS).rev().collect());
+}
+
+/// Parks `object` in the generated `micropython_rs_gc_roots` table (declared
+/// as a `MICROPY_PORT_ROOT_POINTERS` entry) so the GC's conservative stack
+/// scan doesn't need to see it.
+///
+/// Without this, a `Value` that escapes the native call stack — stashed in a
+/// `Vec`, or living inside another Rust heap allocation — would be reachable
+/// only from the Rust heap, which `gc_helper_collect_regs_and_stack` never
+/// looks at, so a later collection could free it out from under its `Value`.
+fn claim_root(object: mp_sys::mp_obj_t) -> usize {
+    let slot = FREE_ROOTS
+        .with(|free| free.borrow_mut().pop())
+        .unwrap_or_else(|| panic!("all {MAX_ROOTS} GC root slots are in use; drop some `Value`s"));
+    unsafe { mp_sys::mp_state_ctx.vm.micropython_rs_gc_roots[slot] = object };
+    slot
+}
+
+fn release_root(slot: usize) {
+    unsafe { mp_sys::mp_state_ctx.vm.micropython_rs_gc_roots[slot] = core::ptr::null_mut() };
+    FREE_ROOTS.with(|free| free.borrow_mut().push(slot));
+}
+
+/// A MicroPython object living on a [`Vm`](crate::Vm)'s heap.
+///
+/// `Value` is a thin, typed wrapper around `mp_obj_t`. The `'vm` lifetime
+/// ties it to the VM whose heap it was allocated on (or whose `exec`
+/// produced it), so it can't outlive the VM that owns its memory. That alone
+/// doesn't keep the GC from collecting it, though, so every `Value` also
+/// claims a slot in `micropython_rs_gc_roots` for as long as it's alive (see
+/// [`claim_root`]).
+pub struct Value<'vm> {
+    pub(crate) object: mp_sys::mp_obj_t,
+    root: usize,
+    _phantom: PhantomData<&'vm ()>,
+}
+
+impl<'vm> Value<'vm> {
+    pub(crate) fn wrap(object: mp_sys::mp_obj_t) -> Self {
+        Self {
+            root: claim_root(object),
+            object,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Unwraps the underlying `mp_obj_t`, for code that hands values back
+    /// across the C ABI boundary (e.g. a native function's return value).
+    ///
+    /// Releases this `Value`'s root slot: the raw object is about to be
+    /// handed straight back into MicroPython's own call machinery, where
+    /// it's reachable from the native call stack (or the VM's own object
+    /// graph) again, so it no longer needs this table to stay alive.
+    pub(crate) fn into_raw(self) -> mp_sys::mp_obj_t {
+        let object = self.object;
+        release_root(self.root);
+        core::mem::forget(self);
+        object
+    }
+
+    pub fn int(value: i64) -> Self {
+        Self::wrap(unsafe { mp_sys::mp_obj_new_int_from_ll(value) })
+    }
+
+    pub fn float(value: f64) -> Self {
+        Self::wrap(unsafe { mp_sys::mp_obj_new_float_from_f64(value) })
+    }
+
+    pub fn bool(value: bool) -> Self {
+        Self::wrap(unsafe {
+            if value {
+                mp_sys::mp_const_true
+            } else {
+                mp_sys::mp_const_false
+            }
+        })
+    }
+
+    pub fn str(value: &str) -> Self {
+        Self::wrap(unsafe { mp_sys::mp_obj_new_str(value.as_ptr() as _, value.len()) })
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        unsafe { mp_sys::mp_obj_is_int(self.object) }.then(|| unsafe {
+            mp_sys::mp_obj_get_int_truncated(self.object) as i64
+        })
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        let mut out = 0f64;
+        unsafe { mp_sys::mp_obj_get_float_maybe(self.object, &mut out) }.then_some(out)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        unsafe { mp_sys::mp_obj_is_bool(self.object) }
+            .then(|| unsafe { mp_sys::mp_obj_is_true(self.object) })
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        if !unsafe { mp_sys::mp_obj_is_str(self.object) } {
+            return None;
+        }
+
+        let mut len = 0usize;
+        let ptr = unsafe { mp_sys::mp_obj_str_get_data(self.object, &mut len) };
+        let bytes = unsafe { core::slice::from_raw_parts(ptr as *const u8, len) };
+        core::str::from_utf8(bytes).ok()
+    }
+
+    /// Iterates `self` using the normal Python iteration protocol, so lists
+    /// and tuples yield their elements and dicts yield their keys.
+    pub fn iter(&self) -> ValueIter<'vm> {
+        let iter = unsafe { mp_sys::mp_getiter(self.object, core::ptr::null_mut()) };
+        ValueIter {
+            root: claim_root(iter),
+            iter,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl Drop for Value<'_> {
+    fn drop(&mut self) {
+        release_root(self.root);
+    }
+}
+
+/// The iterator object itself (not just the `Value`s it yields) is rooted
+/// for the same reason a `Value` is: once a `ValueIter` is stashed somewhere
+/// other than the native call stack, nothing else keeps `iter` alive between
+/// calls to `next`.
+pub struct ValueIter<'vm> {
+    iter: mp_sys::mp_obj_t,
+    root: usize,
+    _phantom: PhantomData<&'vm ()>,
+}
+
+impl<'vm> Iterator for ValueIter<'vm> {
+    type Item = Value<'vm>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = unsafe { mp_sys::mp_iternext(self.iter) };
+        if item.is_null() {
+            None
+        } else {
+            Some(Value::wrap(item))
+        }
+    }
+}
+
+impl Drop for ValueIter<'_> {
+    fn drop(&mut self) {
+        release_root(self.root);
+    }
+}
@@ -4,6 +4,7 @@ use std::{
     fs::{self, File},
     io::Write,
     path::{Path, PathBuf},
+    process::Command,
 };
 
 use anyhow::Result;
@@ -13,7 +14,7 @@ use serde::Serialize;
 mod module;
 mod qstr;
 
-use module::Module;
+use module::{Module, ModuleBinding};
 use qstr::QStr;
 
 struct Data {
@@ -68,6 +69,8 @@ pub struct Config {
     pub bytes_in_hash: BytesIn,
     pub bytes_in_string: BytesIn,
     pub extra_qstrs: Vec<String>,
+    pub split_heap: bool,
+    pub frozen_modules: Vec<PathBuf>,
 }
 
 impl Config {
@@ -75,6 +78,33 @@ impl Config {
         self.extra_qstrs.push(qstr.to_string());
         self
     }
+
+    /// Enables `MICROPY_GC_SPLIT_HEAP` so the VM can grow its heap at
+    /// runtime by registering additional regions with `gc_add`, instead of
+    /// being limited to the span it was initialized with.
+    pub fn split_heap(mut self, enable: bool) -> Self {
+        self.split_heap = enable;
+        self
+    }
+
+    /// Ships `path`'s precompiled bytecode with the firmware so `import`
+    /// resolves it without a filesystem. The module is named after the
+    /// file's stem, e.g. `freeze("lib/sensors.py")` is importable as
+    /// `import sensors`.
+    pub fn freeze(mut self, path: impl Into<PathBuf>) -> Self {
+        self.frozen_modules.push(path.into());
+        self
+    }
+
+    /// Names `import` will resolve once the modules passed to
+    /// [`Config::freeze`] have been compiled in by [`Build::build`].
+    pub fn frozen_module_names(&self) -> impl Iterator<Item = &str> {
+        self.frozen_modules.iter().map(|path| {
+            path.file_stem()
+                .and_then(|stem| stem.to_str())
+                .expect("frozen module path has a UTF-8 file name")
+        })
+    }
     fn is_header_used(&self, path: &Path) -> bool {
         for suffix in [
             "py/dynruntime.h",
@@ -101,6 +131,14 @@ pub struct Build {
     config: Config,
 }
 
+/// The result of running [`Build::freeze_modules`]: the qstrs the frozen
+/// sources introduced (which must join the pool before `qstrdefs.generated.h`
+/// is finalized) and the generated source that embeds their bytecode.
+struct FrozenData {
+    qstrs: Vec<QStr>,
+    content_file: PathBuf,
+}
+
 #[derive(Serialize)]
 struct ExtractedData {
     pub static_qstrs: Vec<QStr>,
@@ -109,6 +147,7 @@ struct ExtractedData {
     pub modules: Vec<Module>,
     pub extensible_modules: Vec<Module>,
     pub module_delegations: Vec<Module>,
+    pub module_bindings: Vec<ModuleBinding>,
 }
 
 impl Build {
@@ -204,6 +243,7 @@ impl Build {
 
         let mut qstrs = qstr_extractor.finish();
         let modules = module_extractor.finish();
+        let module_bindings = modules.bindings();
 
         for qstr in &self.config.extra_qstrs {
             qstrs.unsorted_qstrs.push(QStr::new(
@@ -229,6 +269,61 @@ impl Build {
             modules: modules.modules,
             extensible_modules: modules.extensible_modules,
             module_delegations: modules.module_delegations,
+            module_bindings,
+        })
+    }
+
+    /// Cross-compiles each `Config::freeze`-d `.py` file to bytecode with
+    /// `mpy-cross`, then runs the vendored `tools/mpy-tool.py -f` over the
+    /// results to produce a `frozen_content.c` embedding that bytecode and
+    /// the qstrs it introduced.
+    fn freeze_modules(&self) -> Result<FrozenData> {
+        let genhdr_dir = self.include_dir.join("genhdr");
+        let mpy_cross = self.source_dir.join("mpy-cross/build/mpy-cross");
+        let mpy_tool = self.source_dir.join("tools/mpy-tool.py");
+
+        let mut mpy_files = Vec::new();
+        for py_file in &self.config.frozen_modules {
+            let stem = py_file
+                .file_stem()
+                .expect("frozen module path has a file name");
+            let mpy_file = genhdr_dir.join(stem).with_extension("mpy");
+            let status = Command::new(&mpy_cross)
+                .arg("-o")
+                .arg(&mpy_file)
+                .arg(py_file)
+                .status()?;
+            anyhow::ensure!(
+                status.success(),
+                "mpy-cross failed to compile {}",
+                py_file.display()
+            );
+            mpy_files.push(mpy_file);
+        }
+
+        let output = Command::new("python3")
+            .arg(&mpy_tool)
+            .arg("-f")
+            .arg("-q")
+            .arg(genhdr_dir.join("qstrdefs.generated.h"))
+            .args(&mpy_files)
+            .output()?;
+        anyhow::ensure!(output.status.success(), "mpy-tool.py failed to freeze modules");
+
+        let content_file = genhdr_dir.join("frozen_content.c");
+        fs::write(&content_file, &output.stdout)?;
+
+        // mpy-tool.py reports the qstrs the frozen modules introduced (that
+        // weren't already known) on stderr, one per line, so they can join
+        // the qstr pool before `qstrdefs.generated.h` is finalized.
+        let qstrs = String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .map(|name| QStr::new(&self.config, &self.data, name, 1, "Frozen".to_string()))
+            .collect();
+
+        Ok(FrozenData {
+            qstrs,
+            content_file,
         })
     }
 
@@ -286,7 +381,27 @@ impl Build {
         for (header, _) in GEN_HEADERS {
             let _file = File::create(self.include_dir.join(header))?;
         }
-        let data = self.extract_data()?;
+        let mut data = self.extract_data()?;
+
+        if !self.config.frozen_modules.is_empty() {
+            // `freeze_modules` shells out to `mpy-tool.py -q genhdr/qstrdefs.generated.h`
+            // to learn which qstrs the frozen modules introduce that aren't
+            // already known, so that file needs to hold the qstrs we just
+            // extracted from the C sources, not the empty placeholder above.
+            let mut file = File::create(self.include_dir.join("genhdr/qstrdefs.generated.h"))?;
+            file.write_all(
+                reg.render_template(
+                    include_str!("../templates/qstrdefs.generated.h.tmpl"),
+                    &data,
+                )?
+                .as_bytes(),
+            )?;
+
+            let frozen = self.freeze_modules()?;
+            data.all_qstrs.extend(frozen.qstrs.iter().cloned());
+            data.unsorted_qstrs.extend(frozen.qstrs);
+            self.source_files.push(frozen.content_file);
+        }
 
         for (header, template) in GEN_HEADERS {
             let mut file = File::create(self.include_dir.join(header))?;
@@ -317,6 +432,15 @@ impl Build {
                 .as_bytes(),
         )?;
 
+        // Generate typed Rust handles for the modules extracted from
+        // `MP_REGISTER_MODULE*`, so callers can refer to them by name
+        // instead of looking up raw qstr integers.
+        let mut file = File::create(out_path.join("module.rs"))?;
+        file.write_all(
+            reg.render_template(include_str!("../templates/module.rs.tmpl"), &data)?
+                .as_bytes(),
+        )?;
+
         Ok(())
     }
 
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use anyhow::Result;
 use regex::Regex;
 use serde::Serialize;
@@ -16,6 +18,85 @@ pub struct ExtractedModules {
     pub module_delegations: Vec<Module>,
 }
 
+/// Which `MP_REGISTER_MODULE*` macro a [`ModuleBinding`] came from, i.e. the
+/// generation context its Rust binding needs: a plain top-level module, or
+/// one that accepts extra user code (`MICROPY_PY_..._EXTENSIBLE`).
+///
+/// `MP_REGISTER_MODULE_DELEGATION` has no variant here: it extends an
+/// existing module's attribute lookup rather than registering a second
+/// importable module, so it has no binding of its own (see
+/// [`ExtractedModules::bindings`]).
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModuleMode {
+    TopLevel,
+    Extensible,
+}
+
+/// Schema consumed by `templates/module.rs.tmpl` to emit a typed Rust handle
+/// for an extracted module, spanning the importable `MP_REGISTER_MODULE*`
+/// kinds (see [`ModuleMode`]).
+#[derive(Debug, Serialize)]
+pub struct ModuleBinding {
+    pub mod_ident: String,
+    pub qstr_ident: String,
+    pub symbol: String,
+    pub source: String,
+    pub mode: ModuleMode,
+}
+
+impl ModuleBinding {
+    fn from_module(module: &Module, mode: ModuleMode) -> Self {
+        Self {
+            mod_ident: Self::mod_ident(&module.upper_name),
+            qstr_ident: module.qstr_ident.clone(),
+            symbol: module.symbol.clone(),
+            source: module.source.clone(),
+            mode,
+        }
+    }
+
+    fn mod_ident(upper_name: &str) -> String {
+        let ident: String = upper_name
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        match ident.chars().next() {
+            Some(c) if c.is_ascii_digit() => format!("_{ident}"),
+            _ => ident,
+        }
+    }
+}
+
+impl ExtractedModules {
+    /// Flattens the importable module partitions into the single schema the
+    /// Rust module-binding template walks, tagging each entry with the
+    /// generation context (`mode`) it needs.
+    ///
+    /// `module_delegations` is deliberately left out: `MP_REGISTER_MODULE_DELEGATION`
+    /// extends an *existing* module's attribute lookup rather than
+    /// registering a second importable module under its own qstr, so it
+    /// isn't import-root-worthy and has no binding of its own to generate.
+    /// The remaining partitions are deduped by qstr, since the same module
+    /// qstr isn't expected to show up as both a plain and an extensible
+    /// registration.
+    pub fn bindings(&self) -> Vec<ModuleBinding> {
+        let mut seen_qstrs = HashSet::new();
+        self.modules
+            .iter()
+            .map(|m| (m, ModuleMode::TopLevel))
+            .chain(
+                self.extensible_modules
+                    .iter()
+                    .map(|m| (m, ModuleMode::Extensible)),
+            )
+            .filter(|(m, _)| seen_qstrs.insert(m.qstr_ident.clone()))
+            .map(|(m, mode)| ModuleBinding::from_module(m, mode))
+            .collect()
+    }
+}
+
 pub struct Extractor {
     re: Regex,
     modules: Vec<Module>,
@@ -65,3 +146,53 @@ impl Extractor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mod_ident_lowercases() {
+        assert_eq!(ModuleBinding::mod_ident("OS"), "os");
+        assert_eq!(ModuleBinding::mod_ident("UOS"), "uos");
+    }
+
+    #[test]
+    fn mod_ident_replaces_non_alphanumeric_chars() {
+        assert_eq!(ModuleBinding::mod_ident("U-JSON"), "u_json");
+        assert_eq!(ModuleBinding::mod_ident("U.JSON"), "u_json");
+    }
+
+    #[test]
+    fn mod_ident_prefixes_a_leading_digit() {
+        assert_eq!(ModuleBinding::mod_ident("3DMATH"), "_3dmath");
+    }
+
+    #[test]
+    fn bindings_excludes_delegations_and_dedupes_by_qstr() {
+        let modules = ExtractedModules {
+            modules: vec![Module {
+                qstr_ident: "MP_QSTR_os".to_string(),
+                upper_name: "OS".to_string(),
+                symbol: "mp_module_os".to_string(),
+                source: "py/modos.c".to_string(),
+            }],
+            extensible_modules: vec![Module {
+                qstr_ident: "MP_QSTR_os".to_string(),
+                upper_name: "OS".to_string(),
+                symbol: "mp_module_os".to_string(),
+                source: "py/modos.c".to_string(),
+            }],
+            module_delegations: vec![Module {
+                qstr_ident: "MP_QSTR_uos".to_string(),
+                upper_name: "UOS".to_string(),
+                symbol: "mp_module_os".to_string(),
+                source: "py/modos.c".to_string(),
+            }],
+        };
+
+        let bindings = modules.bindings();
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(bindings[0].qstr_ident, "MP_QSTR_os");
+    }
+}
@@ -7,7 +7,7 @@ use serde::Serialize;
 
 use super::{BytesIn, Config, Data};
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct QStr {
     pub pool: u8,
     pub val: String,
@@ -1,8 +1,5 @@
-use core::str;
 use std::{ffi::c_void, pin::pin};
 
-use libc::size_t;
-
 use micropython::{Vm, VmState};
 use micropython_sys as mp;
 
@@ -15,16 +12,8 @@ mod qstr {
     include!(concat!(env!("OUT_DIR"), "/qstr.rs"));
 }
 
-/// # Safety
-#[no_mangle]
-pub unsafe extern "C" fn mp_hal_stdout_tx_strn_cooked(string: *const u8, len: size_t) {
-    unsafe {
-        let string = core::slice::from_raw_parts(string, len);
-        let Ok(string) = str::from_utf8(&string[..len]) else {
-            return;
-        };
-        print!("{string}");
-    }
+mod module {
+    include!(concat!(env!("OUT_DIR"), "/module.rs"));
 }
 
 /// Run a garbage collection cycle.
@@ -53,7 +42,7 @@ fn main() {
             "print('hello world!', list(x + 1 for x in range(10)), end='eol\\n')",
         )
         .unwrap();
-    vm.exec(&mut obj);
-    vm.exec(&mut obj);
-    vm.exec(&mut obj);
+    vm.exec(&mut obj).unwrap();
+    vm.exec(&mut obj).unwrap();
+    vm.exec(&mut obj).unwrap();
 }